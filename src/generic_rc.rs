@@ -1,13 +1,122 @@
+use core::alloc::Layout;
 use core::cell::{Cell, UnsafeCell};
-use core::marker::{PhantomData, PhantomPinned};
+use core::convert::Infallible;
+use core::marker::{PhantomData, PhantomPinned, Unsize};
+use core::mem::MaybeUninit;
+use core::ops::CoerceUnsized;
 use core::pin::Pin;
-use core::ptr::NonNull;
+use core::ptr::{addr_of_mut, NonNull};
 use core::sync::atomic::AtomicUsize;
 use core::sync::atomic::Ordering::{Acquire, Relaxed, Release};
 use radium::Radium;
 
 const MAX_REFCOUNT: usize = usize::MAX / 2;
 
+/// Sentinel count value stored while a [`PinUniqueHandle`] is outstanding. It is
+/// deliberately above [`MAX_REFCOUNT`] so that any concurrent `create_handle`
+/// observes it as an overflow and aborts rather than aliasing the unique handle.
+const UNIQUE: usize = MAX_REFCOUNT + 1;
+
+/// An in-place initializer for a value of type `T`.
+///
+/// This mirrors the kernel's pin-init API: instead of constructing a `T` and
+/// moving it into its final resting place, an initializer is handed the raw
+/// address the value will live at and fills it in directly. That makes it
+/// possible to build values that are too large to pass through a register or
+/// that borrow their own (now stable) address.
+///
+/// # Safety
+///
+/// An implementer of `__pinned_init` must uphold the following contract:
+/// - on `Ok(())`, `slot` points to a fully initialized, valid `T`;
+/// - on `Err(_)`, `slot` is left untouched and must not be treated as
+///   initialized by the caller.
+///
+/// Callers must pass a `slot` that is properly aligned and valid for writes.
+pub unsafe trait PinInit<T, E = Infallible> {
+    /// Initialize the value at `slot`.
+    ///
+    /// # Safety
+    ///
+    /// `slot` must be properly aligned and valid for writes, and the caller
+    /// must not assume `slot` is initialized unless this returns `Ok(())`.
+    unsafe fn __pinned_init(self, slot: *mut T) -> Result<(), E>;
+}
+
+/// A [`PinInit`] built from a closure, produced by [`pin_init_from_closure`].
+pub struct InitClosure<F, T, E>(F, PhantomData<fn(*mut T) -> E>);
+
+// SAFETY: we simply forward the raw slot to the closure, whose own contract is
+// the one required of `__pinned_init`.
+unsafe impl<T, E, F> PinInit<T, E> for InitClosure<F, T, E>
+where
+    F: FnOnce(*mut T) -> Result<(), E>,
+{
+    unsafe fn __pinned_init(self, slot: *mut T) -> Result<(), E> {
+        (self.0)(slot)
+    }
+}
+
+/// Build a [`PinInit`] from a closure that initializes a raw slot imperatively.
+///
+/// The closure must honour the same contract as [`PinInit::__pinned_init`]: on
+/// `Ok(())` the slot is fully initialized, on `Err(_)` it is left untouched.
+pub fn pin_init_from_closure<T, E, F>(f: F) -> InitClosure<F, T, E>
+where
+    F: FnOnce(*mut T) -> Result<(), E>,
+{
+    InitClosure(f, PhantomData)
+}
+
+/// Stack-allocated, drop-tracking slot for a storage, used by
+/// [`stack_pin_init!`](crate::stack_pin_init).
+///
+/// It owns the `MaybeUninit` backing the storage and remembers whether
+/// initialization succeeded, so that the storage's `Drop` (and therefore the
+/// abort-on-early-drop check) runs exactly when the storage was actually
+/// initialized. This is an implementation detail of the macro and not meant to
+/// be used directly.
+#[doc(hidden)]
+pub struct StackInitGuard<S> {
+    slot: MaybeUninit<S>,
+    initialized: bool,
+}
+
+impl<S> StackInitGuard<S> {
+    /// Create an empty, uninitialized guard.
+    pub fn uninit() -> Self {
+        Self {
+            slot: MaybeUninit::uninit(),
+            initialized: false,
+        }
+    }
+
+    /// Raw pointer to the backing storage slot.
+    pub fn as_mut_ptr(&mut self) -> *mut S {
+        self.slot.as_mut_ptr()
+    }
+
+    /// Mark the slot initialized and obtain a pinned reference to it.
+    ///
+    /// # Safety
+    ///
+    /// The slot must have been fully initialized (e.g. by a successful
+    /// [`PinRcGenericStorage::try_pin_init`]) before this is called.
+    pub unsafe fn assume_init_pin(&mut self) -> Pin<&mut S> {
+        self.initialized = true;
+        Pin::new_unchecked(self.slot.assume_init_mut())
+    }
+}
+
+impl<S> Drop for StackInitGuard<S> {
+    fn drop(&mut self) {
+        if self.initialized {
+            // SAFETY: `initialized` is only set once the slot holds a valid `S`.
+            unsafe { self.slot.assume_init_drop() }
+        }
+    }
+}
+
 /// The common implementation shared by [PinRcStorage](crate::PinRcStorage) and [PinArcStorage](crate::PinArcStorage).
 pub struct PinRcGenericStorage<T, C: Radium<Item = usize>> {
     inner: UnsafeCell<Inner<T, C>>,
@@ -15,12 +124,16 @@ pub struct PinRcGenericStorage<T, C: Radium<Item = usize>> {
     _ps: PhantomData<*const u32>, // prevent Send and Sync
 }
 
-pub(crate) struct Inner<T, C> {
+// `repr(C)` fixes the field order so `value` sits right after `count`, at an
+// offset computable from `C`'s layout and the value's own layout; `from_raw`
+// relies on this to recover the `Inner` pointer from a pointer to `value`.
+#[repr(C)]
+pub(crate) struct Inner<T: ?Sized, C> {
     count: C,
     value: T,
 }
 
-impl<T, C: Radium<Item = usize>> Inner<T, C> {
+impl<T: ?Sized, C: Radium<Item = usize>> Inner<T, C> {
     pub(crate) fn count(&self) -> usize {
         self.count.load(Relaxed)
     }
@@ -77,6 +190,34 @@ impl<T, C: Radium<Item = usize>> PinRcGenericStorage<T, C> {
         }
     }
 
+    /// Initialize a storage in place at `slot` using the given [`PinInit`].
+    ///
+    /// This is the building block used by [`stack_pin_init!`](crate::stack_pin_init);
+    /// most users want that macro rather than calling this directly. The `count`
+    /// is written to zero *before* the value is initialized, so that no handle
+    /// can ever observe an uninitialized count, and the value slot is only
+    /// touched by the initializer.
+    ///
+    /// # Safety
+    ///
+    /// - `slot` must be properly aligned and valid for writes for
+    ///   `PinRcGenericStorage<T, C>`.
+    /// - On `Ok(())` the storage at `slot` is fully initialized and must be
+    ///   treated as pinned from then on. On `Err(_)` only `count` has been
+    ///   written; the `value` is uninitialized, so the caller must not run the
+    ///   storage's `Drop`.
+    pub unsafe fn try_pin_init<E>(
+        slot: *mut Self,
+        init: impl PinInit<T, E>,
+    ) -> Result<(), E> {
+        let inner = UnsafeCell::raw_get(addr_of_mut!((*slot).inner));
+        // Publish a zero count before the value exists: a handle may only be
+        // created once the storage is pinned, but writing the count first means
+        // it is never read as garbage.
+        addr_of_mut!((*inner).count).write(C::new(0));
+        init.__pinned_init(addr_of_mut!((*inner).value))
+    }
+
     /// Get a mutable reference to the contents if there are no handles referring to `self`.
     pub fn get_pin_mut(self: Pin<&mut Self>) -> Option<Pin<&mut T>> {
         if self.as_ref().inner_unpin().count.load(Acquire) == 0 {
@@ -86,6 +227,33 @@ impl<T, C: Radium<Item = usize>> PinRcGenericStorage<T, C> {
         }
     }
 
+    /// Create a handle with statically-guaranteed exclusive access, if there
+    /// are no handles referring to `self`.
+    ///
+    /// On success the count is moved from `0` to a reserved sentinel so that no
+    /// [`create_handle`](crate::PinRcGenericStorage::create_handle) or
+    /// [`get_pin_mut`](Self::get_pin_mut) can alias the returned handle while it
+    /// lives — the returned [`PinUniqueHandle`] therefore grants `&mut`/pinned
+    /// access with no per-access check, and can later be
+    /// [`share`](PinUniqueHandle::share)d into an ordinary shared handle.
+    ///
+    /// This takes `Pin<&mut Self>`: the returned handle borrows the storage
+    /// exclusively for its whole lifetime, so the storage (and its shared
+    /// `Deref`) cannot be touched while the unique handle exists.
+    pub fn create_unique_handle(self: Pin<&mut Self>) -> Option<PinUniqueHandle<'_, T, C>> {
+        let inner = self.as_ref().inner_pin().get_ref();
+        match inner
+            .count
+            .compare_exchange(0, UNIQUE, Acquire, Relaxed)
+        {
+            Ok(_) => Some(PinUniqueHandle {
+                inner: NonNull::from(inner),
+                _p: PhantomData,
+            }),
+            Err(_) => None,
+        }
+    }
+
     pub(crate) fn inner_pin(self: Pin<&Self>) -> Pin<&Inner<T, C>> {
         unsafe { Pin::new_unchecked(&*self.inner.get()) }
     }
@@ -96,9 +264,9 @@ impl<T, C: Radium<Item = usize>> PinRcGenericStorage<T, C> {
 }
 
 /// The common implementation shared by [PinRc](crate::PinRc) and [PinArc](crate::PinArc).
-pub struct PinRcGeneric<T, C: Radium<Item = usize>>(NonNull<Inner<T, C>>);
+pub struct PinRcGeneric<T: ?Sized, C: Radium<Item = usize>>(NonNull<Inner<T, C>>);
 
-impl<T, C: Radium<Item = usize>> PinRcGeneric<T, C> {
+impl<T: ?Sized, C: Radium<Item = usize>> PinRcGeneric<T, C> {
     pub(crate) fn inner_pin(&self) -> Pin<&Inner<T, C>> {
         unsafe { Pin::new_unchecked(self.0.as_ref()) }
     }
@@ -106,21 +274,202 @@ impl<T, C: Radium<Item = usize>> PinRcGeneric<T, C> {
     pub(crate) fn inner_unpin(&self) -> &Inner<T, C> {
         self.inner_pin().get_ref()
     }
+
+    /// Consume this handle and return a raw pointer to the stored value,
+    /// without decrementing the refcount.
+    ///
+    /// The returned pointer points at the `value` field inside the storage. The
+    /// count is left as-is (the strong reference this handle represented now
+    /// belongs to the raw pointer), so the pointer must eventually be passed
+    /// back to [`from_raw`](Self::from_raw) to release it. The pointer is only
+    /// valid while the storage lives, consistent with the crate's
+    /// abort-on-early-drop model.
+    pub fn into_raw(self) -> NonNull<T> {
+        let ptr = NonNull::from(self.inner_unpin().value_unpin());
+        core::mem::forget(self);
+        ptr
+    }
+
+    /// Reconstruct a handle from a pointer previously returned by
+    /// [`into_raw`](Self::into_raw).
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have come from `into_raw` on a handle for a storage that is
+    /// still alive, and must not have been passed to `from_raw` already. The
+    /// count is not touched: the strong reference represented by `ptr` is
+    /// simply re-wrapped.
+    pub unsafe fn from_raw(ptr: NonNull<T>) -> Self {
+        let value = ptr.as_ptr();
+        // `value` is the second field of the `repr(C)` `Inner`; recover the
+        // offset from the leading `count: C` using the value's own layout, then
+        // walk back to the `Inner` header while preserving the pointer metadata.
+        let value_layout = Layout::for_value_raw(value as *const T);
+        let (_, offset) = Layout::new::<C>().extend(value_layout).unwrap();
+        let inner = value.byte_sub(offset) as *mut Inner<T, C>;
+        PinRcGeneric(NonNull::new_unchecked(inner))
+    }
+
+    /// Turn this handle into one that derefs to a sub-field of the stored value,
+    /// while keeping the storage alive.
+    ///
+    /// The projection closure receives `&T` and must return a reference into
+    /// that same value (e.g. `|s| &s.field`). The resulting [`PinRcProjected`]
+    /// holds the storage's refcount just like the original handle did — this
+    /// handle's contribution is transferred into it, so no atomic traffic is
+    /// needed here. It is analogous to the kernel's `container_of!` dance for
+    /// moving between a containing object and one of its members.
+    pub fn map<U, F>(self, f: F) -> PinRcProjected<U, C>
+    where
+        U: ?Sized,
+        F: FnOnce(&T) -> &U,
+    {
+        let inner = self.inner_unpin();
+        let value = NonNull::from(f(inner.value_unpin()));
+        let count = NonNull::from(&inner.count);
+        // The count we already own moves into the projected handle untouched.
+        core::mem::forget(self);
+        PinRcProjected { count, value }
+    }
 }
 
-impl<T, C: Radium<Item = usize>> Drop for PinRcGeneric<T, C> {
+// A handle coerces to a handle over any `U` that `T` unsizes to (e.g.
+// `PinArc<Concrete>` to `PinArc<dyn Trait>`), just like `Arc` does: the inner
+// `NonNull` is itself `CoerceUnsized`, and the refcount lives in the same
+// allocation regardless of the pointer's metadata.
+impl<T, U, C> CoerceUnsized<PinRcGeneric<U, C>> for PinRcGeneric<T, C>
+where
+    T: Unsize<U> + ?Sized,
+    U: ?Sized,
+    C: Radium<Item = usize>,
+{
+}
+
+impl<T: ?Sized, C: Radium<Item = usize>> Drop for PinRcGeneric<T, C> {
     fn drop(&mut self) {
         let c = self.inner_unpin().count.fetch_sub(1, Release);
         debug_assert!(c > 0);
     }
 }
 
+/// A handle that derefs to a sub-field `U` of a value stored in a
+/// `Pin{Rc|Arc}Storage`, produced by [`PinRcGeneric::map`].
+///
+/// It carries two pointers into the same allocation: one to the refcount
+/// (used only to keep the storage alive) and one to the projected field
+/// returned by `Deref`. Like every handle in this crate, dropping it after the
+/// storage has gone is prevented by the storage's abort-on-early-drop model.
+pub struct PinRcProjected<U: ?Sized, C: Radium<Item = usize>> {
+    count: NonNull<C>,
+    value: NonNull<U>,
+}
+
+impl<U: ?Sized, C: Radium<Item = usize>> PinRcProjected<U, C> {
+    /// Get the number of handles currently referring to the underlying storage.
+    /// Beware of race conditions, as with [`PinRcGeneric::ref_count`](crate::PinRcGeneric::ref_count).
+    pub fn ref_count(&self) -> usize {
+        unsafe { self.count.as_ref() }.load(Relaxed)
+    }
+}
+
+impl<U: ?Sized, C: Radium<Item = usize>> core::ops::Deref for PinRcProjected<U, C> {
+    type Target = U;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { self.value.as_ref() }
+    }
+}
+
+impl<U: ?Sized, C: Radium<Item = usize>> Clone for PinRcProjected<U, C> {
+    fn clone(&self) -> Self {
+        let old_count = unsafe { self.count.as_ref() }.fetch_add(1, Relaxed);
+        if old_count > MAX_REFCOUNT {
+            abort()
+        }
+        PinRcProjected {
+            count: self.count,
+            value: self.value,
+        }
+    }
+}
+
+impl<U: ?Sized, C: Radium<Item = usize>> Drop for PinRcProjected<U, C> {
+    fn drop(&mut self) {
+        let c = unsafe { self.count.as_ref() }.fetch_sub(1, Release);
+        debug_assert!(c > 0);
+    }
+}
+
+/// A uniquely-owning handle to a storage's value, produced by
+/// [`create_unique_handle`](PinRcGenericStorage::create_unique_handle).
+///
+/// While it exists it is statically the only handle to the storage, so it
+/// hands out `&mut`/pinned access without any runtime recheck. Call
+/// [`share`](Self::share) to publish it as an ordinary shared
+/// [`PinRcGeneric`]. Dropping it without sharing releases the storage back to
+/// its unreferenced state.
+///
+/// It borrows the storage exclusively for `'a`, which is what makes the
+/// `&mut T` it hands out sound: no shared reference to the storage (and hence
+/// no `&T` via its `Deref`) can coexist.
+pub struct PinUniqueHandle<'a, T: ?Sized, C: Radium<Item = usize>> {
+    inner: NonNull<Inner<T, C>>,
+    _p: PhantomData<&'a mut Inner<T, C>>,
+}
+
+impl<'a, T: ?Sized, C: Radium<Item = usize>> PinUniqueHandle<'a, T, C> {
+    /// Get pinned, exclusive access to the stored value.
+    pub fn as_pin_mut(&mut self) -> Pin<&mut T> {
+        // SAFETY: the sentinel count guarantees we are the sole handle, and the
+        // value is pinned for as long as the storage lives.
+        unsafe { Pin::new_unchecked(&mut (*self.inner.as_ptr()).value) }
+    }
+
+    /// Downgrade this unique handle into an ordinary shared handle, without a
+    /// runtime check: the count moves directly from the unique sentinel to `1`.
+    pub fn share(self) -> PinRcGeneric<T, C> {
+        let inner = self.inner;
+        unsafe { inner.as_ref() }.count.store(1, Release);
+        core::mem::forget(self);
+        PinRcGeneric(inner)
+    }
+}
+
+impl<'a, T: ?Sized, C: Radium<Item = usize>> core::ops::Deref for PinUniqueHandle<'a, T, C> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &self.inner.as_ref().value }
+    }
+}
+
+impl<'a, T: ?Sized, C: Radium<Item = usize>> core::ops::DerefMut for PinUniqueHandle<'a, T, C> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: we are the sole handle; exclusive access is guaranteed.
+        unsafe { &mut (*self.inner.as_ptr()).value }
+    }
+}
+
+impl<'a, T: ?Sized, C: Radium<Item = usize>> Drop for PinUniqueHandle<'a, T, C> {
+    fn drop(&mut self) {
+        // Release the unique reservation, returning the storage to its
+        // unreferenced state.
+        unsafe { self.inner.as_ref() }.count.store(0, Release);
+    }
+}
+
 pub type PinRc<T> = PinRcGeneric<T, Cell<usize>>;
 pub type PinRcStorage<T> = PinRcGenericStorage<T, Cell<usize>>;
 pub type PinArc<T> = PinRcGeneric<T, AtomicUsize>;
 pub type PinArcStorage<T> = PinRcGenericStorage<T, AtomicUsize>;
 
-unsafe impl<T> Sync for PinArc<T> where T: Sync {}
+unsafe impl<T: ?Sized> Sync for PinArc<T> where T: Sync {}
 unsafe impl<T> Sync for PinArcStorage<T> where T: Sync {}
-unsafe impl<T> Send for PinArc<T> where T: Sync {}
+unsafe impl<T: ?Sized> Send for PinArc<T> where T: Sync {}
 unsafe impl<T> Send for PinArcStorage<T> where T: Send + Sync {}
+
+unsafe impl<U: ?Sized> Sync for PinRcProjected<U, AtomicUsize> where U: Sync {}
+unsafe impl<U: ?Sized> Send for PinRcProjected<U, AtomicUsize> where U: Sync {}
+
+unsafe impl<T: ?Sized> Sync for PinUniqueHandle<'_, T, AtomicUsize> where T: Sync {}
+unsafe impl<T: ?Sized> Send for PinUniqueHandle<'_, T, AtomicUsize> where T: Send {}
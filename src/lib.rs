@@ -1,5 +1,6 @@
 #![no_std]
 #![deny(unsafe_code)]
+#![feature(coerce_unsized, unsize, layout_for_ptr)]
 
 //! This crate provides reference counting pointers similar to `Rc` and `Arc`, but without heap allocation.
 //! You are responsible for creating a `Pin{Arc|Rc}Storage`, which you can obtain `Pin{Arc|Rc}` pointers from.
@@ -48,7 +49,52 @@ pub type PinArcStorage<T> = PinRcGenericStorage<T, AtomicUsize>;
 mod generic_rc;
 
 use crate::generic_rc::Inner;
-pub use generic_rc::{PinRcGeneric, PinRcGenericStorage};
+pub use generic_rc::{
+    pin_init_from_closure, InitClosure, PinInit, PinRcGeneric, PinRcGenericStorage,
+    PinRcProjected, PinUniqueHandle, StackInitGuard,
+};
+
+/// Lay out a `Pin{Rc|Arc}Storage` on the stack and initialize it in place with
+/// a [`PinInit`].
+///
+/// This is the pinned-initialization analogue of [`pin`](core::pin::pin): the
+/// storage is built at its final address, so its contents may borrow that
+/// address. The storage type must be named explicitly (so the count kind `C`
+/// is fixed), e.g. `PinArcStorage<u32>` or `PinRcStorage<_>`. The macro binds
+/// `$storage` to a `Result<Pin<&mut _>, E>`; on `Err` the value was never
+/// initialized. On `Ok` the storage's `Drop` runs at the end of the enclosing
+/// scope, preserving the crate's abort-on-early-drop guarantee; on `Err`
+/// nothing is dropped, since there is no initialized value.
+///
+/// ```rust
+/// # use pin_rc::{pin_init_from_closure, stack_pin_init, PinArcStorage};
+/// # use std::convert::Infallible;
+/// stack_pin_init!(storage: PinArcStorage<u32> = pin_init_from_closure(|slot: *mut u32| {
+///     unsafe { slot.write(4) };
+///     Ok::<(), Infallible>(())
+/// }));
+/// let storage = storage.unwrap();
+/// let arc = storage.as_ref().create_handle();
+/// assert_eq!(*arc, 4);
+/// ```
+#[macro_export]
+macro_rules! stack_pin_init {
+    ($storage:ident : $ty:ty = $init:expr) => {
+        let mut $storage = $crate::StackInitGuard::<$ty>::uninit();
+        // SAFETY: `slot` points at the guard's freshly declared storage, valid
+        // for writes. We only call `assume_init_pin` on the `Ok` path, which
+        // the `try_pin_init` contract guarantees leaves the storage
+        // initialized; the guard then drops that storage at scope end.
+        let $storage = unsafe {
+            match <$ty>::try_pin_init($storage.as_mut_ptr(), $init) {
+                ::core::result::Result::Ok(()) => {
+                    ::core::result::Result::Ok($storage.assume_init_pin())
+                }
+                ::core::result::Result::Err(e) => ::core::result::Result::Err(e),
+            }
+        };
+    };
+}
 
 impl<T, C: Radium<Item = usize>> Deref for PinRcGenericStorage<T, C> {
     type Target = T;
@@ -81,7 +127,7 @@ impl<T, C: Radium<Item = usize>> PinRcGenericStorage<T, C> {
     }
 }
 
-impl<T, C: Radium<Item = usize>> PinRcGeneric<T, C> {
+impl<T: ?Sized, C: Radium<Item = usize>> PinRcGeneric<T, C> {
     /// Get the number of handles currently referring to the same storage (including `self`).
     /// Beware of race conditions:
     /// Concurrent operations may change the count between
@@ -91,7 +137,7 @@ impl<T, C: Radium<Item = usize>> PinRcGeneric<T, C> {
     }
 }
 
-impl<T, C: Radium<Item = usize>> Deref for PinRcGeneric<T, C> {
+impl<T: ?Sized, C: Radium<Item = usize>> Deref for PinRcGeneric<T, C> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
@@ -99,15 +145,22 @@ impl<T, C: Radium<Item = usize>> Deref for PinRcGeneric<T, C> {
     }
 }
 
-impl<T, C: Radium<Item = usize>> Clone for PinRcGeneric<T, C> {
+impl<T: ?Sized, C: Radium<Item = usize>> Clone for PinRcGeneric<T, C> {
     fn clone(&self) -> Self {
         self.inner().create_handle()
     }
 }
 
+// The `$($maybe:tt)*` tail carries the extra `T` bound that differs between the
+// two targets: `?Sized` for the handle (which supports DSTs) and nothing for
+// the always-`Sized` storage.
 macro_rules! impl_cmp_trait {
-    ($Trait:ident{$($name:ident->$Ret:ty),*} for $For:ident) => {
-        impl<T:$Trait,C:Radium<Item=usize>>  $Trait for $For<T,C>{
+    ($Trait:ident{$($name:ident->$Ret:ty),*} for $For:ident; $($maybe:tt)*) => {
+        impl<T, C: Radium<Item = usize>> $Trait for $For<T, C>
+        where
+            T: $Trait,
+            T: $($maybe)*
+        {
             $(
                 #[inline]
                 fn $name(&self, other: &Self)->$Ret{
@@ -118,31 +171,42 @@ macro_rules! impl_cmp_trait {
     };
 }
 
-impl_cmp_trait!(PartialEq{eq->bool} for PinRcGeneric);
-impl_cmp_trait!(Eq{} for PinRcGeneric);
-impl_cmp_trait!(PartialOrd{partial_cmp->Option<Ordering>,lt->bool,le->bool,gt->bool,ge->bool} for PinRcGeneric);
-impl_cmp_trait!(Ord{cmp->Ordering} for PinRcGeneric);
+impl_cmp_trait!(PartialEq{eq->bool} for PinRcGeneric; ?Sized);
+impl_cmp_trait!(Eq{} for PinRcGeneric; ?Sized);
+impl_cmp_trait!(PartialOrd{partial_cmp->Option<Ordering>,lt->bool,le->bool,gt->bool,ge->bool} for PinRcGeneric; ?Sized);
+impl_cmp_trait!(Ord{cmp->Ordering} for PinRcGeneric; ?Sized);
 
-impl_cmp_trait!(PartialEq{eq->bool} for PinRcGenericStorage);
-impl_cmp_trait!(Eq{} for PinRcGenericStorage);
-impl_cmp_trait!(PartialOrd{partial_cmp->Option<Ordering>,lt->bool,le->bool,gt->bool,ge->bool} for PinRcGenericStorage);
-impl_cmp_trait!(Ord{cmp->Ordering} for PinRcGenericStorage);
+impl_cmp_trait!(PartialEq{eq->bool} for PinRcGenericStorage;);
+impl_cmp_trait!(Eq{} for PinRcGenericStorage;);
+impl_cmp_trait!(PartialOrd{partial_cmp->Option<Ordering>,lt->bool,le->bool,gt->bool,ge->bool} for PinRcGenericStorage;);
+impl_cmp_trait!(Ord{cmp->Ordering} for PinRcGenericStorage;);
 
 macro_rules! impl_others {
-    ($For:ident) => {
-        impl<T: Hash, C: Radium<Item = usize>> Hash for $For<T, C> {
+    ($For:ident; $($maybe:tt)*) => {
+        impl<T, C: Radium<Item = usize>> Hash for $For<T, C>
+        where
+            T: Hash,
+            T: $($maybe)*
+        {
             fn hash<H: Hasher>(&self, state: &mut H) {
                 <T as Hash>::hash(&**self, state)
             }
         }
 
-        impl<T, C: Radium<Item = usize>> Borrow<T> for $For<T, C> {
+        impl<T, C: Radium<Item = usize>> Borrow<T> for $For<T, C>
+        where
+            T: $($maybe)*
+        {
             fn borrow(&self) -> &T {
                 self
             }
         }
 
-        impl<T: Debug, C: Radium<Item = usize>> Debug for $For<T, C> {
+        impl<T, C: Radium<Item = usize>> Debug for $For<T, C>
+        where
+            T: Debug,
+            T: $($maybe)*
+        {
             fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
                 Debug::fmt(self.inner(), f)
             }
@@ -150,14 +214,14 @@ macro_rules! impl_others {
     };
 }
 
-impl_others!(PinRcGeneric);
-impl_others!(PinRcGenericStorage);
+impl_others!(PinRcGeneric; ?Sized);
+impl_others!(PinRcGenericStorage;);
 
-impl<T: Debug, C: Radium<Item = usize>> Debug for Inner<T, C> {
+impl<T: ?Sized + Debug, C: Radium<Item = usize>> Debug for Inner<T, C> {
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         let mut s = f.debug_struct("PinRcGeneric");
         s.field("ref_count", &self.count());
-        s.field("value", self.value());
+        s.field("value", &self.value());
         s.finish()
     }
 }
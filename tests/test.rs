@@ -1,4 +1,5 @@
-use pin_rc::PinRcStorage;
+use pin_rc::{pin_init_from_closure, stack_pin_init, PinRcStorage};
+use std::convert::Infallible;
 use std::pin::pin;
 
 #[test]
@@ -26,3 +27,85 @@ fn use_after_drop() {
         x.as_ref().create_handle()
     };
 }
+
+#[test]
+fn stack_pin_init_ok() {
+    stack_pin_init!(x: PinRcStorage<u32> = pin_init_from_closure(|slot: *mut u32| {
+        unsafe { slot.write(7) };
+        Ok::<(), Infallible>(())
+    }));
+    let x = x.unwrap();
+    assert_eq!(x.ref_count(), 0);
+    let h = x.as_ref().create_handle();
+    assert_eq!(*h, 7);
+}
+
+#[test]
+fn stack_pin_init_err() {
+    stack_pin_init!(x: PinRcStorage<Vec<u8>> = pin_init_from_closure(|_slot: *mut Vec<u8>| Err("nope")));
+    assert!(x.is_err());
+}
+
+#[test]
+fn unsized_handle() {
+    use pin_rc::{PinRc, PinRcStorage};
+    let storage = pin!(PinRcStorage::new(|| 42u32));
+    let handle: PinRc<dyn Fn() -> u32> = storage.as_ref().create_handle();
+    assert_eq!(handle(), 42);
+    assert_eq!(storage.ref_count(), 1);
+}
+
+#[test]
+fn projected_handle() {
+    struct Pair {
+        a: u32,
+        b: u32,
+    }
+    let storage = pin!(PinRcStorage::new(Pair { a: 1, b: 2 }));
+    let h = storage.as_ref().create_handle();
+    let b = h.map(|p| &p.b);
+    assert_eq!(*b, 2);
+    assert_eq!(storage.ref_count(), 1);
+    let b2 = b.clone();
+    assert_eq!(storage.ref_count(), 2);
+    drop(b);
+    drop(b2);
+    assert_eq!(storage.ref_count(), 0);
+}
+
+#[test]
+fn into_from_raw_roundtrip() {
+    let storage = pin!(PinRcStorage::new(99u32));
+    let h = storage.as_ref().create_handle();
+    assert_eq!(storage.ref_count(), 1);
+    let raw = h.into_raw();
+    // `into_raw` does not touch the count.
+    assert_eq!(storage.ref_count(), 1);
+    assert_eq!(unsafe { *raw.as_ref() }, 99);
+    let h = unsafe { pin_rc::PinRc::from_raw(raw) };
+    assert_eq!(*h, 99);
+    assert_eq!(storage.ref_count(), 1);
+    drop(h);
+    assert_eq!(storage.ref_count(), 0);
+}
+
+#[test]
+fn unique_handle_then_share() {
+    let mut storage = pin!(PinRcStorage::new(10u32));
+    let shared = {
+        // The unique handle borrows the storage exclusively; no other access is
+        // possible while it is alive (enforced at compile time).
+        let mut u = storage.as_mut().create_unique_handle().unwrap();
+        *u += 5;
+        assert_eq!(*u, 15);
+        u.share()
+    };
+    assert_eq!(*shared, 15);
+    assert_eq!(shared.ref_count(), 1);
+    drop(shared);
+    assert_eq!(storage.ref_count(), 0);
+    // Dropping a unique handle without sharing releases the reservation.
+    let u = storage.as_mut().create_unique_handle().unwrap();
+    drop(u);
+    assert_eq!(storage.ref_count(), 0);
+}